@@ -0,0 +1,193 @@
+/**
+ * Compressed, versioned export/import bundle for the whole collection.
+ *
+ * Bundles every row of the unified store (tags, category, and the
+ * saved/favorite/recent flags) into a single zstd-compressed JSON
+ * manifest, so a user's whole library can be backed up or shared as one
+ * portable file instead of hand-copying files out of the app data dir.
+ *
+ * Author: KleaSCM
+ * Email: KleaSCM@gmail.com
+ */
+
+use crate::storage::{FlaggedEntry, Storage};
+use crate::AtomicSave;
+
+/// Bumped whenever `ExportManifest`'s shape changes in an incompatible way.
+const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(non_snake_case)]
+struct ExportManifest {
+	SchemaVersion: u32,
+	Kaomojis: Vec<FlaggedEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(non_snake_case)]
+pub enum MergeStrategy {
+	/// Wipe the existing store and replace it with the archive's contents.
+	Replace,
+	/// Upsert by `Character`, unioning tags and OR-ing flags.
+	Merge,
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn ExportLibrary(storage: tauri::State<Storage>, destPath: String) -> Result<(), String> {
+	let kaomojis = storage.LoadAllFlagged()?;
+	let manifest = ExportManifest { SchemaVersion: EXPORT_SCHEMA_VERSION, Kaomojis: kaomojis };
+
+	let json = serde_json::to_vec(&manifest).map_err(|e| e.to_string())?;
+	let compressed = zstd::stream::encode_all(&json[..], 19).map_err(|e| e.to_string())?;
+
+	AtomicSave(std::path::Path::new(&destPath), &compressed)
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn ImportLibrary(storage: tauri::State<Storage>, srcPath: String, mergeStrategy: MergeStrategy) -> Result<usize, String> {
+	let srcPath = std::path::Path::new(&srcPath);
+	let compressed = std::fs::read(srcPath).map_err(|e| e.to_string())?;
+
+	let json = zstd::stream::decode_all(&compressed[..]).map_err(|e| e.to_string())?;
+
+	let manifest = parseManifest(&json)?;
+
+	let applied = match mergeStrategy {
+		MergeStrategy::Replace => storage.ReplaceAllFlagged(manifest.Kaomojis)?,
+		MergeStrategy::Merge => storage.MergeAllFlagged(manifest.Kaomojis)?,
+	};
+
+	Ok(applied)
+}
+
+/// Decodes and validates a decompressed export manifest. Unlike every other
+/// `BackupCorrupt` caller, `srcPath` here is a file the user picked outside
+/// `app_data_dir`, not an app-owned file — so a manifest that fails to parse
+/// just returns an error and leaves it alone instead of renaming it aside.
+/// A manifest from an incompatible schema version does parse fine, it's
+/// just not ours to apply, so it's rejected with an error too.
+#[allow(non_snake_case)]
+fn parseManifest(json: &[u8]) -> Result<ExportManifest, String> {
+	let manifest: ExportManifest =
+		serde_json::from_slice(json).map_err(|e| format!("Archive manifest could not be parsed: {e}"))?;
+
+	if manifest.SchemaVersion != EXPORT_SCHEMA_VERSION {
+		return Err(format!(
+			"Unsupported export schema version {} (expected {})",
+			manifest.SchemaVersion, EXPORT_SCHEMA_VERSION
+		));
+	}
+
+	Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testutil::openTempStorage;
+
+	fn sampleEntry() -> FlaggedEntry {
+		FlaggedEntry {
+			Character: ":)".to_string(),
+			Tags: vec!["cute".to_string(), "happy".to_string()],
+			Category: "emotions".to_string(),
+			Saved: true,
+			Favorite: false,
+			Recent: true,
+			UseCount: 4,
+			LastUsedMillis: 123_456,
+		}
+	}
+
+	#[test]
+	fn replace_and_merge_count_skip_blank_character_rows() {
+		let blank = FlaggedEntry { Character: "  ".to_string(), ..sampleEntry() };
+
+		let (_replaceDir, replaceStorage) = openTempStorage("count_replace");
+		let replaceApplied = replaceStorage.ReplaceAllFlagged(vec![sampleEntry(), blank.clone()]).unwrap();
+		assert_eq!(replaceApplied, 1);
+
+		let (_mergeDir, mergeStorage) = openTempStorage("count_merge");
+		let mergeApplied = mergeStorage.MergeAllFlagged(vec![sampleEntry(), blank]).unwrap();
+		assert_eq!(mergeApplied, 1);
+	}
+
+	#[test]
+	fn wrong_schema_version_is_rejected_not_applied() {
+		let manifest = ExportManifest { SchemaVersion: EXPORT_SCHEMA_VERSION + 1, Kaomojis: vec![sampleEntry()] };
+		let json = serde_json::to_vec(&manifest).unwrap();
+
+		let err = parseManifest(&json).unwrap_err();
+		assert!(err.contains("Unsupported export schema version"));
+	}
+
+	#[test]
+	fn export_then_import_round_trip_preserves_tags_flags_and_use_count() {
+		let (_sourceDir, source) = openTempStorage("round_trip_source");
+		source.ReplaceAllFlagged(vec![sampleEntry()]).unwrap();
+
+		let exported = source.LoadAllFlagged().unwrap();
+		let manifest = ExportManifest { SchemaVersion: EXPORT_SCHEMA_VERSION, Kaomojis: exported };
+		let json = serde_json::to_vec(&manifest).unwrap();
+
+		let roundTripped = parseManifest(&json).unwrap();
+
+		let (_destDir, dest) = openTempStorage("round_trip_dest");
+		dest.ReplaceAllFlagged(roundTripped.Kaomojis).unwrap();
+
+		let loaded = dest.LoadAllFlagged().unwrap();
+		assert_eq!(loaded.len(), 1);
+		assert_eq!(loaded[0].Character, ":)");
+		assert_eq!(loaded[0].Tags, vec!["cute".to_string(), "happy".to_string()]);
+		assert_eq!(loaded[0].Category, "emotions");
+		assert!(loaded[0].Saved);
+		assert!(!loaded[0].Favorite);
+		assert!(loaded[0].Recent);
+		assert_eq!(loaded[0].UseCount, 4);
+		assert_eq!(loaded[0].LastUsedMillis, 123_456);
+	}
+
+	#[test]
+	fn merge_unions_tags_and_ors_flags_instead_of_overwriting() {
+		let (_dir, storage) = openTempStorage("merge_unions_instead_of_overwrites");
+		storage
+			.ReplaceAllFlagged(vec![FlaggedEntry {
+				Character: ":)".to_string(),
+				Tags: vec!["happy".to_string()],
+				Category: "emotions".to_string(),
+				Saved: true,
+				Favorite: false,
+				Recent: false,
+				UseCount: 5,
+				LastUsedMillis: 1_000,
+			}])
+			.unwrap();
+
+		storage
+			.MergeAllFlagged(vec![FlaggedEntry {
+				Character: ":)".to_string(),
+				Tags: vec!["cute".to_string()],
+				Category: "faces".to_string(),
+				Saved: false,
+				Favorite: true,
+				Recent: true,
+				UseCount: 2,
+				LastUsedMillis: 2_000,
+			}])
+			.unwrap();
+
+		let merged = storage.LoadAllFlagged().unwrap();
+		assert_eq!(merged.len(), 1);
+		// Tags union instead of the import's tags replacing the existing set.
+		assert_eq!(merged[0].Tags, vec!["cute".to_string(), "happy".to_string()]);
+		// Flags OR instead of the import's flags overwriting the existing ones.
+		assert!(merged[0].Saved);
+		assert!(merged[0].Favorite);
+		assert!(merged[0].Recent);
+		// Frecency counters take the max instead of being overwritten either way.
+		assert_eq!(merged[0].UseCount, 5);
+		assert_eq!(merged[0].LastUsedMillis, 2_000);
+	}
+}
@@ -26,7 +26,16 @@
  * Email: KleaSCM@gmail.com
  */
 
-#[derive(serde::Serialize, serde::Deserialize)]
+mod export;
+mod recovery;
+mod search;
+#[cfg(test)]
+mod testutil;
+mod storage;
+
+use storage::Storage;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[allow(non_snake_case)]
 struct KaomojiEntry {
 	Character: String,
@@ -34,6 +43,17 @@ struct KaomojiEntry {
 	Category: String,
 }
 
+/// Shared tag sanitization: trim, lowercase, drop empties. Used by every
+/// command that writes tags so a kaomoji's tags are normalized the same
+/// way regardless of which command touched it.
+#[allow(non_snake_case)]
+fn NormalizeTags(tags: Vec<String>) -> Vec<String> {
+	tags.into_iter()
+		.map(|t| t.trim().to_lowercase())
+		.filter(|t| !t.is_empty())
+		.collect()
+}
+
 #[tauri::command]
 #[allow(non_snake_case)]
 fn Greet(name: &str) -> String {
@@ -79,13 +99,16 @@ fn BackupCorrupt(filePath: &std::path::Path) {
 
 /**
  * ATOMIC REPLACEMENT STRATEGY
- * 
- * Ensures that file writes are individual and complete. 
+ *
+ * Ensures that file writes are individual and complete.
  * On Windows, uses ReplaceFileW for atomicity when replacing existing files.
  * Falls back to MoveFileExW with durability flags.
+ *
+ * Takes raw bytes rather than `&str` so it doubles as the writer for binary
+ * archives (see export.rs), not just JSON text.
  */
 #[allow(non_snake_case)]
-fn AtomicSave(filePath: &std::path::Path, content: &str) -> Result<(), String> {
+fn AtomicSave(filePath: &std::path::Path, content: &[u8]) -> Result<(), String> {
 	use std::{fs, io::Write, time::{SystemTime, UNIX_EPOCH}};
 
 	// Unique temp name to prevent race conditions
@@ -105,10 +128,19 @@ fn AtomicSave(filePath: &std::path::Path, content: &str) -> Result<(), String> {
 	// DURABILITY STRATEGY: Write + Flush
 	{
 		let mut f = fs::File::create(&tmpPath).map_err(|e| e.to_string())?;
-		f.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+		f.write_all(content).map_err(|e| e.to_string())?;
 		f.sync_all().map_err(|e| e.to_string())?;
 	}
 
+	// STRATEGY: Directory Durability (Metadata Sync)
+	//
+	// Also sync on the new-file path, not just after a successful rename:
+	// if the data dir was just created, the tmp file above is the first
+	// directory entry written into it, and that entry needs its own fsync
+	// to be durably recorded before we go on to rename over it.
+	#[cfg(not(windows))]
+	syncParentDir(&tmpPath);
+
 	#[cfg(windows)]
 	let saveResult = {
 		use std::os::windows::ffi::OsStrExt;
@@ -169,14 +201,8 @@ fn AtomicSave(filePath: &std::path::Path, content: &str) -> Result<(), String> {
 	#[cfg(not(windows))]
 	let saveResult = {
 		// Unix-like systems: rename is atomic and replaces destination by default
-		fs::rename(&tmpPath, filePath).map_err(|e| e.to_string()).and_then(|_| {
-			// STRATEGY: Directory Durability (Metadata Sync)
-			if let Some(parent) = filePath.parent() {
-				if let Ok(dir) = fs::File::open(parent) {
-					let _ = dir.sync_all(); // Best effort metadata flush
-				}
-			}
-			Ok(())
+		fs::rename(&tmpPath, filePath).map_err(|e| e.to_string()).map(|_| {
+			syncParentDir(filePath);
 		})
 	};
 
@@ -186,247 +212,156 @@ fn AtomicSave(filePath: &std::path::Path, content: &str) -> Result<(), String> {
 	saveResult
 }
 
-#[tauri::command]
+/// Best-effort fsync of `path`'s parent directory metadata (unix only;
+/// Windows' `ReplaceFileW`/`MoveFileExW` handle durability themselves).
+#[cfg(not(windows))]
 #[allow(non_snake_case)]
-fn LoadUserKaomojis(app: tauri::AppHandle) -> Result<Vec<KaomojiEntry>, String> {
-	use tauri::Manager;
-	use std::fs;
-
-	// BINDING CONTRACT: Ensure dataDir exists for consistency
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
+fn syncParentDir(path: &std::path::Path) {
+	if let Some(parent) = path.parent() {
+		if let Ok(dir) = std::fs::File::open(parent) {
+			let _ = dir.sync_all();
+		}
 	}
+}
 
-	let filePath = dataDir.join("kaomojis.user.json");
-	if !filePath.exists() {
-		return Ok(Vec::new());
-	}
+/**
+ * COMMANDS: thin wrappers over `Storage`.
+ *
+ * Loads/single-entry mutations/batch mutations all funnel through the same
+ * `Storage` methods now, so there is exactly one place (storage.rs) that
+ * knows about the db schema.
+ */
 
-	let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-	
-	// SAFE CORRUPTION HANDLING
-	let list: Vec<KaomojiEntry> = match serde_json::from_str(&content) {
-		Ok(v) => v,
-		Err(_) => {
-			BackupCorrupt(&filePath);
-			Vec::new()
-		}
-	};
-	
-	Ok(list)
+#[tauri::command]
+#[allow(non_snake_case)]
+fn LoadUserKaomojis(storage: tauri::State<Storage>) -> Result<Vec<KaomojiEntry>, String> {
+	storage.LoadUser()
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-fn SaveKaomoji(app: tauri::AppHandle, mut newEntry: KaomojiEntry) -> Result<(), String> {
-	use tauri::Manager;
-	use std::fs;
-
-	// STRATEGY: Deep Sanitization
-	newEntry.Character = newEntry.Character.trim().to_string();
-	if newEntry.Character.is_empty() {
-		return Err("Kaomoji character cannot be empty".to_string());
-	}
-
-	newEntry.Tags = newEntry
-		.Tags
-		.into_iter()
-		.map(|t| t.trim().to_lowercase())
-		.filter(|t| !t.is_empty())
-		.collect();
-
-	newEntry.Category = newEntry.Category.trim().to_string();
-
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
-	}
-
-	let filePath = dataDir.join("kaomojis.user.json");
-	let mut list: Vec<KaomojiEntry> = if filePath.exists() {
-		let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-		
-		match serde_json::from_str(&content) {
-			Ok(v) => v,
-			Err(_) => {
-				BackupCorrupt(&filePath);
-				Vec::new()
-			}
-		}
-	} else {
-		Vec::new()
-	};
-
-	// Uart-style Upsert: Update if exists, else push
-	let exists = list.iter_mut().find(|k| k.Character == newEntry.Character);
-	match exists {
-		Some(item) => {
-			item.Tags = newEntry.Tags;
-			item.Category = newEntry.Category;
-		}
-		None => list.push(newEntry),
-	}
-
-	let updatedContent = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
-	AtomicSave(&filePath, &updatedContent)?;
-
-	Ok(())
+fn SaveKaomoji(storage: tauri::State<Storage>, newEntry: KaomojiEntry) -> Result<(), String> {
+	storage.SaveUser(vec![newEntry]).map(|mut results| results.remove(0))?
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-fn LoadRecents(app: tauri::AppHandle) -> Result<Vec<KaomojiEntry>, String> {
-	use tauri::Manager;
-	use std::fs;
-
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
-	}
+fn SaveKaomojis(storage: tauri::State<Storage>, newEntries: Vec<KaomojiEntry>) -> Result<Vec<Result<(), String>>, String> {
+	storage.SaveUser(newEntries)
+}
 
-	let filePath = dataDir.join("kaomojis.recents.json");
-	if !filePath.exists() {
-		return Ok(Vec::new());
-	}
+/// Halves a recent's ranking weight every this many days by default; see
+/// `Storage::TouchRecents`/`Storage::LoadRecents`.
+const DEFAULT_FRECENCY_HALF_LIFE_DAYS: f64 = 7.0;
 
-	let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-	
-	let list: Vec<KaomojiEntry> = match serde_json::from_str(&content) {
-		Ok(v) => v,
-		Err(_) => {
-			BackupCorrupt(&filePath);
-			Vec::new()
-		}
-	};
-	
-	Ok(list)
+#[tauri::command]
+#[allow(non_snake_case)]
+fn LoadRecents(storage: tauri::State<Storage>, frecencyHalfLifeDays: Option<f64>) -> Result<Vec<KaomojiEntry>, String> {
+	storage.LoadRecents(frecencyHalfLifeDays.unwrap_or(DEFAULT_FRECENCY_HALF_LIFE_DAYS))
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-fn SaveRecent(app: tauri::AppHandle, entry: KaomojiEntry) -> Result<(), String> {
-	use tauri::Manager;
-	use std::fs;
-
-	const MAX_RECENTS: usize = 20;
-
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
-	}
-
-	let filePath = dataDir.join("kaomojis.recents.json");
-	let mut list: Vec<KaomojiEntry> = if filePath.exists() {
-		let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-		
-		match serde_json::from_str(&content) {
-			Ok(v) => v,
-			Err(_) => {
-				BackupCorrupt(&filePath);
-				Vec::new()
-			}
-		}
-	} else {
-		Vec::new()
-	};
-
-	// Remove any existing instance of this kaomoji (by Character)
-	list.retain(|k| k.Character != entry.Character);
-	
-	// Add to front of list
-	list.insert(0, entry);
-	
-	// Trim to max size
-	list.truncate(MAX_RECENTS);
-
-	let updatedContent = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
-	AtomicSave(&filePath, &updatedContent)?;
-
-	Ok(())
+fn SaveRecent(storage: tauri::State<Storage>, entry: KaomojiEntry, frecencyHalfLifeDays: Option<f64>) -> Result<(), String> {
+	storage
+		.TouchRecents(vec![entry], MAX_RECENTS, frecencyHalfLifeDays.unwrap_or(DEFAULT_FRECENCY_HALF_LIFE_DAYS))
+		.map(|mut results| results.remove(0))?
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-fn LoadFavorites(app: tauri::AppHandle) -> Result<Vec<KaomojiEntry>, String> {
-	use tauri::Manager;
-	use std::fs;
-
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
-	}
-
-	let filePath = dataDir.join("kaomojis.favorites.json");
-	if !filePath.exists() {
-		return Ok(Vec::new());
-	}
+fn SaveRecents(storage: tauri::State<Storage>, entries: Vec<KaomojiEntry>, frecencyHalfLifeDays: Option<f64>) -> Result<Vec<Result<(), String>>, String> {
+	storage.TouchRecents(entries, MAX_RECENTS, frecencyHalfLifeDays.unwrap_or(DEFAULT_FRECENCY_HALF_LIFE_DAYS))
+}
 
-	let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-	
-	let list: Vec<KaomojiEntry> = match serde_json::from_str(&content) {
-		Ok(v) => v,
-		Err(_) => {
-			BackupCorrupt(&filePath);
-			Vec::new()
-		}
-	};
-	
-	Ok(list)
+#[tauri::command]
+#[allow(non_snake_case)]
+fn LoadFavorites(storage: tauri::State<Storage>) -> Result<Vec<KaomojiEntry>, String> {
+	storage.LoadFavorites()
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-fn ToggleFavorite(app: tauri::AppHandle, entry: KaomojiEntry) -> Result<bool, String> {
-	use tauri::Manager;
-	use std::fs;
+fn ToggleFavorite(storage: tauri::State<Storage>, entry: KaomojiEntry) -> Result<bool, String> {
+	storage.ToggleFavorites(vec![entry]).map(|mut results| results.remove(0))?
+}
 
-	let dataDir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-	if !dataDir.exists() {
-		fs::create_dir_all(&dataDir).map_err(|e| e.to_string())?;
-	}
+#[tauri::command]
+#[allow(non_snake_case)]
+fn ToggleFavorites(storage: tauri::State<Storage>, entries: Vec<KaomojiEntry>) -> Result<Vec<Result<bool, String>>, String> {
+	storage.ToggleFavorites(entries)
+}
 
-	let filePath = dataDir.join("kaomojis.favorites.json");
-	let mut list: Vec<KaomojiEntry> = if filePath.exists() {
-		let content = fs::read_to_string(&filePath).map_err(|e| e.to_string())?;
-		
-		match serde_json::from_str(&content) {
-			Ok(v) => v,
-			Err(_) => {
-				BackupCorrupt(&filePath);
-				Vec::new()
-			}
-		}
-	} else {
-		Vec::new()
-	};
+/**
+ * TAG TAXONOMY COMMANDS
+ *
+ * Maintain tags globally instead of entry-by-entry, reusing the trim +
+ * lowercase normalization `SaveKaomoji` already applies. Each returns the
+ * number of affected entries so the UI can confirm the bulk change.
+ */
 
-	// Check if entry exists (by Character)
-	let existingIndex = list.iter().position(|k| k.Character == entry.Character);
-	
-	let isFavorite = if let Some(idx) = existingIndex {
-		// Remove from favorites
-		list.remove(idx);
-		false
-	} else {
-		// Add to favorites
-		list.push(entry);
-		true
-	};
+#[tauri::command]
+#[allow(non_snake_case)]
+fn RenameTag(storage: tauri::State<Storage>, from: String, to: String) -> Result<usize, String> {
+	storage.RenameTag(&from, &to)
+}
 
-	let updatedContent = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
-	AtomicSave(&filePath, &updatedContent)?;
+#[tauri::command]
+#[allow(non_snake_case)]
+fn MergeTags(storage: tauri::State<Storage>, sources: Vec<String>, into: String) -> Result<usize, String> {
+	storage.MergeTags(sources, &into)
+}
 
-	Ok(isFavorite)
+#[tauri::command]
+#[allow(non_snake_case)]
+fn DeleteTag(storage: tauri::State<Storage>, tag: String) -> Result<usize, String> {
+	storage.DeleteTag(&tag)
 }
 
+const MAX_RECENTS: usize = 20;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 #[allow(non_snake_case)]
 pub fn Run() {
 	tauri::Builder::default()
 		.plugin(tauri_plugin_clipboard_manager::init())
 		.plugin(tauri_plugin_opener::init())
-		.invoke_handler(tauri::generate_handler![Greet, CopyToClipboard, SaveKaomoji, LoadUserKaomojis, LoadRecents, SaveRecent, LoadFavorites, ToggleFavorite])
+		.setup(|app| {
+			use tauri::Manager;
+
+			let dataDir = app.path().app_data_dir()?;
+			if !dataDir.exists() {
+				std::fs::create_dir_all(&dataDir)?;
+			}
+
+			recovery::Sweep(&dataDir);
+
+			let storage = Storage::Open(&dataDir.join("kaomojis.db"))?;
+			storage.MigrateFromJson(&dataDir)?;
+			app.manage(storage);
+			app.manage(search::SearchIndex::default());
+
+			Ok(())
+		})
+		.invoke_handler(tauri::generate_handler![
+			Greet,
+			CopyToClipboard,
+			SaveKaomoji,
+			SaveKaomojis,
+			LoadUserKaomojis,
+			LoadRecents,
+			SaveRecent,
+			SaveRecents,
+			LoadFavorites,
+			ToggleFavorite,
+			ToggleFavorites,
+			export::ExportLibrary,
+			export::ImportLibrary,
+			search::SearchKaomojis,
+			RenameTag,
+			MergeTags,
+			DeleteTag
+		])
 		.run(tauri::generate_context!())
 		.expect("error while running tauri application");
 }
@@ -0,0 +1,179 @@
+/**
+ * Startup Crash-Recovery Sweep.
+ *
+ * `AtomicSave` writes a unique `<name>.<pid>.<ts>.tmp` file and removes it
+ * on success, but a crash or power loss between `sync_all` and
+ * `remove_file` leaves it behind forever. `BackupCorrupt` similarly
+ * accumulates `<name>.corrupt.<secs>.bak` files without bound. `Sweep` runs
+ * once at startup and cleans up both.
+ *
+ * Author: KleaSCM
+ * Email: KleaSCM@gmail.com
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Leftover temp files older than this are removed even if their writer's
+/// pid happens to have been reused by an unrelated, still-running process.
+const STALE_TMP_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// How many `.corrupt.N.bak` backups to keep per original file.
+const MAX_CORRUPT_BACKUPS_PER_FILE: usize = 5;
+
+/// Scans `dataDir` for orphaned `AtomicSave` temp files and prunes old
+/// `BackupCorrupt` backups down to the most recent few per base file.
+/// Best-effort: failures to remove an individual file are logged and
+/// skipped rather than aborting the sweep.
+#[allow(non_snake_case)]
+pub fn Sweep(dataDir: &Path) {
+	let entries = match std::fs::read_dir(dataDir) {
+		Ok(entries) => entries,
+		Err(e) => {
+			eprintln!("Startup recovery sweep: could not read {:?}: {}", dataDir, e);
+			return;
+		}
+	};
+
+	let mut corruptBackups: HashMap<String, Vec<(u64, PathBuf)>> = HashMap::new();
+
+	for entry in entries.flatten() {
+		let path = entry.path();
+		let Some(fileName) = path.file_name().and_then(|n| n.to_str()) else {
+			continue;
+		};
+
+		if fileName.ends_with(".tmp") {
+			sweepTmpFile(&path, fileName);
+			continue;
+		}
+
+		if let Some((baseName, timestamp)) = parseCorruptBackupName(fileName) {
+			corruptBackups.entry(baseName).or_default().push((timestamp, path));
+		}
+	}
+
+	for (_, mut backups) in corruptBackups {
+		backups.sort_by(|a, b| b.0.cmp(&a.0));
+		for (_, path) in backups.into_iter().skip(MAX_CORRUPT_BACKUPS_PER_FILE) {
+			if let Err(e) = std::fs::remove_file(&path) {
+				eprintln!("Startup recovery sweep: failed to prune old backup {:?}: {}", path, e);
+			}
+		}
+	}
+}
+
+/// Removes `path` if the pid that created it (encoded in its name) is no
+/// longer live, or if the pid can't be confirmed live (including when the
+/// name doesn't even parse) and the file is simply old enough that we don't
+/// trust it.
+fn sweepTmpFile(path: &Path, fileName: &str) {
+	let isStale = match parseTmpFilePid(fileName) {
+		Some(pid) if isPidLive(pid) => ageSecs(path).map(|age| age > STALE_TMP_AGE_SECS).unwrap_or(false),
+		Some(_) => true,
+		None => ageSecs(path).map(|age| age > STALE_TMP_AGE_SECS).unwrap_or(false),
+	};
+
+	if isStale {
+		if let Err(e) = std::fs::remove_file(path) {
+			eprintln!("Startup recovery sweep: failed to remove orphaned temp file {:?}: {}", path, e);
+		}
+	}
+}
+
+/// `AtomicSave` names its temp files `<original>.<pid>.<ts>.tmp`.
+fn parseTmpFilePid(fileName: &str) -> Option<u32> {
+	let withoutExt = fileName.strip_suffix(".tmp")?;
+	let mut parts = withoutExt.rsplitn(3, '.');
+	let _timestamp = parts.next()?;
+	let pid = parts.next()?;
+	pid.parse::<u32>().ok()
+}
+
+/// `BackupCorrupt` names its backups `<original>.corrupt.<unix-secs>.bak`.
+fn parseCorruptBackupName(fileName: &str) -> Option<(String, u64)> {
+	let withoutExt = fileName.strip_suffix(".bak")?;
+	let (baseName, timestampStr) = withoutExt.rsplit_once(".corrupt.")?;
+	let timestamp = timestampStr.parse::<u64>().ok()?;
+	Some((baseName.to_string(), timestamp))
+}
+
+fn ageSecs(path: &Path) -> Option<u64> {
+	let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+	SystemTime::now().duration_since(modified).ok().map(|d| d.as_secs())
+}
+
+#[cfg(target_os = "linux")]
+fn isPidLive(pid: u32) -> bool {
+	Path::new(&format!("/proc/{pid}")).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isPidLive(_pid: u32) -> bool {
+	// No portable liveness probe without adding a dependency; fall back to
+	// age as the only signal on platforms other than Linux.
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testutil::TempDir;
+
+	#[test]
+	fn parse_tmp_file_pid_parses_a_multi_dot_base_filename() {
+		assert_eq!(parseTmpFilePid("kaomojis.user.json.12345.1690000000.tmp"), Some(12345));
+	}
+
+	#[test]
+	fn parse_tmp_file_pid_rejects_an_unparsable_pid() {
+		assert_eq!(parseTmpFilePid("kaomojis.user.json.not-a-pid.1690000000.tmp"), None);
+	}
+
+	#[test]
+	fn parse_tmp_file_pid_rejects_a_name_missing_the_pid_segment() {
+		assert_eq!(parseTmpFilePid("kaomojis.tmp"), None);
+	}
+
+	#[test]
+	fn parse_tmp_file_pid_rejects_names_without_the_tmp_suffix() {
+		assert_eq!(parseTmpFilePid("kaomojis.user.json.12345.1690000000"), None);
+	}
+
+	#[test]
+	fn parse_corrupt_backup_name_parses_a_multi_dot_base_filename() {
+		assert_eq!(
+			parseCorruptBackupName("kaomojis.user.json.corrupt.1690000000.bak"),
+			Some(("kaomojis.user.json".to_string(), 1690000000))
+		);
+	}
+
+	#[test]
+	fn parse_corrupt_backup_name_rejects_an_unparsable_timestamp() {
+		assert_eq!(parseCorruptBackupName("kaomojis.user.json.corrupt.not-a-timestamp.bak"), None);
+	}
+
+	#[test]
+	fn parse_corrupt_backup_name_rejects_names_missing_the_corrupt_marker() {
+		assert_eq!(parseCorruptBackupName("kaomojis.user.json.bak"), None);
+	}
+
+	#[test]
+	fn sweep_tmp_file_age_gates_an_unparsable_name_instead_of_always_removing_it() {
+		let dir = TempDir::new("sweep_unparsable_name");
+		let fileName = "weird.tmp";
+		let path = dir.0.join(fileName);
+		std::fs::write(&path, b"partial write").unwrap();
+		assert!(parseTmpFilePid(fileName).is_none());
+
+		sweepTmpFile(&path, fileName);
+		assert!(path.exists(), "a fresh file with an unparsable name should not be swept yet");
+
+		let staleTime = SystemTime::now() - std::time::Duration::from_secs(STALE_TMP_AGE_SECS + 60);
+		std::fs::File::open(&path).unwrap().set_modified(staleTime).unwrap();
+
+		sweepTmpFile(&path, fileName);
+		assert!(!path.exists(), "an old enough file with an unparsable name should be swept");
+	}
+}
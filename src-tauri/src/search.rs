@@ -0,0 +1,247 @@
+/**
+ * Backend fuzzy search with a cached tag index.
+ *
+ * `SearchKaomojis` scores every entry against the query with an fzf-style
+ * subsequence matcher: a base point per matched character, a bonus for runs
+ * of consecutive matches, a bonus for matching right after a
+ * separator/case boundary, and a penalty proportional to the gap since the
+ * last match. Per-field scores are summed, weighting tags and category
+ * above the raw glyph.
+ *
+ * The per-entry joined-tag string, plus a lowercased "character + category +
+ * tags" blob, are built once per `Storage` revision and reused across
+ * queries. Before running the full fuzzy scorer, candidates are pruned down
+ * to entries whose blob contains the query's first character — fuzzy
+ * subsequence matching can't be pruned by an exact index, but this first-char
+ * filter is cheap and skips the expensive per-field scoring for entries that
+ * can't possibly match.
+ *
+ * Author: KleaSCM
+ * Email: KleaSCM@gmail.com
+ */
+
+use std::sync::Mutex;
+
+use crate::storage::Storage;
+use crate::KaomojiEntry;
+
+const CATEGORY_WEIGHT: i64 = 2;
+const TAG_WEIGHT: i64 = 3;
+
+struct CachedIndex {
+	revision: u64,
+	entries: Vec<KaomojiEntry>,
+	joinedTags: Vec<String>,
+	/// Lowercased `character + category + joinedTags`, one per entry, used to
+	/// cheaply prune candidates before the full fuzzy scorer runs.
+	lowerBlobs: Vec<String>,
+}
+
+/// Tauri-managed cache of the last-built search index.
+#[derive(Default)]
+pub struct SearchIndex {
+	cache: Mutex<Option<CachedIndex>>,
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn SearchKaomojis(storage: tauri::State<Storage>, index: tauri::State<SearchIndex>, query: String, limit: usize) -> Result<Vec<KaomojiEntry>, String> {
+	let mut cache = index.cache.lock().map_err(|e| e.to_string())?;
+	let revision = storage.Revision();
+
+	let needsRebuild = match cache.as_ref() {
+		Some(cached) => cached.revision != revision,
+		None => true,
+	};
+	if needsRebuild {
+		*cache = Some(buildIndex(storage.LoadAll()?, revision));
+	}
+	let cached = cache.as_ref().expect("index was just built above");
+	let firstQueryChar = query.to_lowercase().chars().next();
+
+	let mut scored: Vec<(i64, usize)> = cached
+		.entries
+		.iter()
+		.enumerate()
+		.filter(|(i, _)| match firstQueryChar {
+			Some(ch) => cached.lowerBlobs[*i].contains(ch),
+			None => true,
+		})
+		.filter_map(|(i, entry)| ScoreEntry(&query, entry, &cached.joinedTags[i]).map(|score| (score, i)))
+		.collect();
+
+	scored.sort_by(|a, b| b.0.cmp(&a.0));
+	scored.truncate(limit);
+
+	Ok(scored.into_iter().map(|(_, i)| cached.entries[i].clone()).collect())
+}
+
+fn buildIndex(entries: Vec<KaomojiEntry>, revision: u64) -> CachedIndex {
+	let joinedTags: Vec<String> = entries.iter().map(|entry| entry.Tags.join(" ")).collect();
+	let lowerBlobs = entries
+		.iter()
+		.zip(&joinedTags)
+		.map(|(entry, tags)| format!("{} {} {}", entry.Character, entry.Category, tags).to_lowercase())
+		.collect();
+
+	CachedIndex { revision, entries, joinedTags, lowerBlobs }
+}
+
+#[allow(non_snake_case)]
+fn ScoreEntry(query: &str, entry: &KaomojiEntry, joinedTags: &str) -> Option<i64> {
+	let characterScore = FuzzyMatch(query, &entry.Character);
+	let categoryScore = FuzzyMatch(query, &entry.Category);
+	let tagScore = FuzzyMatch(query, joinedTags);
+
+	if characterScore.is_none() && categoryScore.is_none() && tagScore.is_none() {
+		return None;
+	}
+
+	Some(characterScore.unwrap_or(0) + categoryScore.unwrap_or(0) * CATEGORY_WEIGHT + tagScore.unwrap_or(0) * TAG_WEIGHT)
+}
+
+/// fzf-style subsequence scorer: `query`'s characters must all appear in
+/// `text` in order (case-insensitively). Returns `None` if `query` isn't a
+/// subsequence of `text`.
+#[allow(non_snake_case)]
+fn FuzzyMatch(query: &str, text: &str) -> Option<i64> {
+	const BASE_POINT: i64 = 10;
+	const CONSECUTIVE_BONUS: i64 = 8;
+	const BOUNDARY_BONUS: i64 = 6;
+	const GAP_PENALTY_PER_CHAR: i64 = 2;
+	const MAX_GAP_PENALIZED: i64 = 10;
+
+	if query.is_empty() {
+		return Some(0);
+	}
+
+	// `text.to_lowercase()` isn't guaranteed to preserve `text.chars().count()`
+	// (e.g. 'İ' expands to two chars), so boundary info is computed per
+	// original char and carried alongside its (possibly multi-char) lowered
+	// form, keeping `textLower`/`isBoundary` index-aligned by construction
+	// instead of re-deriving boundaries from a separately-lowered array.
+	let textOrigChars: Vec<char> = text.chars().collect();
+	let mut textLower: Vec<char> = Vec::with_capacity(textOrigChars.len());
+	let mut isBoundary: Vec<bool> = Vec::with_capacity(textOrigChars.len());
+	for (i, &ch) in textOrigChars.iter().enumerate() {
+		let prevChar = if i == 0 { None } else { textOrigChars.get(i - 1) };
+		let isSeparatorBoundary = i == 0 || matches!(prevChar, Some(' ') | Some('_') | Some('-'));
+		let isCaseBoundary = prevChar.is_some_and(|p| p.is_lowercase()) && ch.is_uppercase();
+		let boundary = isSeparatorBoundary || isCaseBoundary;
+		for lowered in ch.to_lowercase() {
+			textLower.push(lowered);
+			isBoundary.push(boundary);
+		}
+	}
+	let queryLower: Vec<char> = query.to_lowercase().chars().collect();
+
+	let mut score: i64 = 0;
+	let mut queryIdx = 0;
+	let mut lastMatchIdx: Option<usize> = None;
+	let mut consecutiveRun: i64 = 0;
+
+	for (textIdx, &ch) in textLower.iter().enumerate() {
+		if queryIdx >= queryLower.len() {
+			break;
+		}
+		if ch != queryLower[queryIdx] {
+			continue;
+		}
+
+		let mut charScore = BASE_POINT;
+		match lastMatchIdx {
+			Some(last) if textIdx == last + 1 => {
+				consecutiveRun += 1;
+				charScore += CONSECUTIVE_BONUS * consecutiveRun.min(3);
+			}
+			Some(last) => {
+				consecutiveRun = 0;
+				charScore -= GAP_PENALTY_PER_CHAR * (textIdx - last - 1).min(MAX_GAP_PENALIZED as usize) as i64;
+			}
+			None => {}
+		}
+
+		if isBoundary[textIdx] {
+			charScore += BOUNDARY_BONUS;
+		}
+
+		score += charScore;
+		lastMatchIdx = Some(textIdx);
+		queryIdx += 1;
+	}
+
+	if queryIdx == queryLower.len() {
+		Some(score)
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fuzzy_match_rejects_non_subsequence() {
+		assert_eq!(FuzzyMatch("xyz", "hello world"), None);
+	}
+
+	#[test]
+	fn fuzzy_match_accepts_a_gapped_subsequence() {
+		assert!(FuzzyMatch("hlo", "hello").is_some());
+	}
+
+	#[test]
+	fn fuzzy_match_empty_query_matches_everything_with_zero_score() {
+		assert_eq!(FuzzyMatch("", "hello"), Some(0));
+	}
+
+	#[test]
+	fn fuzzy_match_rewards_consecutive_runs_over_scattered_matches() {
+		let consecutive = FuzzyMatch("hel", "hello").unwrap();
+		let scattered = FuzzyMatch("hlo", "hello").unwrap();
+		assert!(consecutive > scattered);
+	}
+
+	#[test]
+	fn fuzzy_match_rewards_word_boundary_matches() {
+		// Single-char queries isolate the boundary bonus from the
+		// consecutive-run/gap-penalty logic, which only kick in once a
+		// second match has happened.
+		let boundaryMatch = FuzzyMatch("w", "hello world").unwrap();
+		let midWordMatch = FuzzyMatch("o", "hello world").unwrap();
+		assert!(boundaryMatch > midWordMatch);
+	}
+
+	#[test]
+	fn score_entry_weights_tag_match_above_character_match() {
+		// The same text matched once in the tags and once in the glyph, so
+		// the field weight is the only difference between the two scores.
+		let tagEntry = KaomojiEntry { Character: "(o_o)".to_string(), Tags: vec!["owo".to_string()], Category: "faces".to_string() };
+		let characterEntry = KaomojiEntry { Character: "owo".to_string(), Tags: vec!["confused".to_string()], Category: "faces".to_string() };
+
+		let tagScore = ScoreEntry("owo", &tagEntry, "owo").unwrap();
+		let characterScore = ScoreEntry("owo", &characterEntry, "confused").unwrap();
+		assert!(tagScore > characterScore);
+	}
+
+	#[test]
+	fn score_entry_returns_none_when_no_field_matches() {
+		let entry = KaomojiEntry { Character: "(o_o)".to_string(), Tags: vec!["confused".to_string()], Category: "faces".to_string() };
+		assert_eq!(ScoreEntry("zzz", &entry, "confused"), None);
+	}
+
+	#[test]
+	fn fuzzy_match_handles_text_whose_lowercasing_changes_char_count() {
+		// 'İ'.to_lowercase() expands to two chars ('i' + combining dot
+		// above), so "İstanbul" has 8 chars but a 9-char lowered form. A
+		// matcher that indexes the lowered form against the original-case
+		// char array would misalign past that point.
+		assert_eq!("İstanbul".chars().count(), 8);
+		assert_eq!("İstanbul".to_lowercase().chars().count(), 9);
+
+		assert!(FuzzyMatch("istanbul", "İstanbul").is_some());
+		assert!(FuzzyMatch("bul", "İstanbul").is_some());
+	}
+}
@@ -0,0 +1,974 @@
+/**
+ * Unified SQLite-Backed Storage.
+ *
+ * Replaces the three parallel JSON files (`kaomojis.user.json`,
+ * `kaomojis.recents.json`, `kaomojis.favorites.json`) with a single
+ * `kaomojis.db`: one `kaomoji` table carrying `saved`/`favorite`/`recent`
+ * flags, plus a `tag` / `kaomoji_tag` join table so a kaomoji's tags are
+ * normalized instead of duplicated per-file.
+ *
+ * Every command's mutation runs inside one transaction and touches only the
+ * rows it changes (saving an entry is a single-row UPSERT), replacing the
+ * old read-whole-file / mutate-one-entry / rewrite-whole-file cycle. The
+ * one-time `MigrateFromJson` import writes row by row without a transaction.
+ *
+ * BACKWARD COMPATIBILITY:
+ * - `MigrateFromJson` runs once at startup (before the db is otherwise
+ *   touched) and imports the three legacy files if present, reusing the
+ *   existing `BackupCorrupt` semantics for anything unparsable.
+ *
+ * Author: KleaSCM
+ * Email: KleaSCM@gmail.com
+ */
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{BackupCorrupt, KaomojiEntry};
+
+pub struct Storage {
+	conn: Mutex<Connection>,
+	/// Bumped on every committed mutation, so callers that cache derived
+	/// views (e.g. the search index) can tell when to rebuild.
+	revision: AtomicU64,
+}
+
+#[allow(non_snake_case)]
+impl Storage {
+	/// Opens (creating if needed) the `kaomojis.db` at `dbPath` and ensures
+	/// the schema exists.
+	pub fn Open(dbPath: &Path) -> Result<Storage, String> {
+		let conn = Connection::open(dbPath).map_err(|e| e.to_string())?;
+		conn.execute_batch(
+			"
+			PRAGMA foreign_keys = ON;
+
+			CREATE TABLE IF NOT EXISTS kaomoji (
+				character        TEXT PRIMARY KEY,
+				category         TEXT NOT NULL DEFAULT '',
+				saved            INTEGER NOT NULL DEFAULT 0,
+				favorite         INTEGER NOT NULL DEFAULT 0,
+				recent           INTEGER NOT NULL DEFAULT 0,
+				use_count        INTEGER NOT NULL DEFAULT 0,
+				last_used_millis INTEGER NOT NULL DEFAULT 0
+			);
+
+			CREATE TABLE IF NOT EXISTS tag (
+				id   INTEGER PRIMARY KEY,
+				name TEXT NOT NULL UNIQUE
+			);
+
+			CREATE TABLE IF NOT EXISTS kaomoji_tag (
+				character TEXT NOT NULL REFERENCES kaomoji(character) ON DELETE CASCADE,
+				tag_id    INTEGER NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+				PRIMARY KEY (character, tag_id)
+			);
+			",
+		)
+		.map_err(|e| e.to_string())?;
+
+		// Forward-compatible upgrade for dbs created before frecency tracking
+		// existed; errors (column already present) are expected and ignored.
+		let _ = conn.execute("ALTER TABLE kaomoji ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0", []);
+		let _ = conn.execute("ALTER TABLE kaomoji ADD COLUMN last_used_millis INTEGER NOT NULL DEFAULT 0", []);
+
+		Ok(Storage { conn: Mutex::new(conn), revision: AtomicU64::new(0) })
+	}
+
+	/// Current revision counter. Bumped after every committed mutation.
+	pub fn Revision(&self) -> u64 {
+		self.revision.load(Ordering::Acquire)
+	}
+
+	fn bumpRevision(&self) {
+		self.revision.fetch_add(1, Ordering::AcqRel);
+	}
+
+	/// One-time import of the legacy `kaomojis.user.json` /
+	/// `kaomojis.recents.json` / `kaomojis.favorites.json` files, if any of
+	/// them are still present next to the db. Imported files are renamed to
+	/// `<name>.migrated` so this only ever runs once.
+	pub fn MigrateFromJson(&self, dataDir: &Path) -> Result<(), String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+		let userPath = dataDir.join("kaomojis.user.json");
+		let recentsPath = dataDir.join("kaomojis.recents.json");
+		let favoritesPath = dataDir.join("kaomojis.favorites.json");
+
+		if !userPath.exists() && !recentsPath.exists() && !favoritesPath.exists() {
+			return Ok(());
+		}
+
+		importLegacyFile(&conn, &userPath, "saved")?;
+		importLegacyFile(&conn, &recentsPath, "recent")?;
+		importLegacyFile(&conn, &favoritesPath, "favorite")?;
+
+		Ok(())
+	}
+
+	/// All entries saved into the user library (`saved = 1`).
+	pub fn LoadUser(&self) -> Result<Vec<KaomojiEntry>, String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+		loadWhere(&conn, "saved = 1", "character")
+	}
+
+	/// Favorited entries (`favorite = 1`).
+	pub fn LoadFavorites(&self) -> Result<Vec<KaomojiEntry>, String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+		loadWhere(&conn, "favorite = 1", "character")
+	}
+
+	/// Recently-used entries (`recent = 1`), ranked by frecency score
+	/// (`use_count * decay(now - last_used_millis)`, see
+	/// `frecencyScore`) rather than plain MRU order.
+	pub fn LoadRecents(&self, halfLifeDays: f64) -> Result<Vec<KaomojiEntry>, String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let nowMs = nowMillis();
+
+		let mut stmt = conn
+			.prepare("SELECT character, category, use_count, last_used_millis FROM kaomoji WHERE recent = 1")
+			.map_err(|e| e.to_string())?;
+		let rows = stmt
+			.query_map([], |row| {
+				Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?))
+			})
+			.map_err(|e| e.to_string())?;
+
+		let mut scored = Vec::new();
+		for row in rows {
+			let (character, category, useCount, lastUsedMillis) = row.map_err(|e| e.to_string())?;
+			let tags = loadTags(&conn, &character)?;
+			let score = frecencyScore(useCount, lastUsedMillis, nowMs, halfLifeDays);
+			scored.push((score, KaomojiEntry { Character: character, Tags: tags, Category: category }));
+		}
+
+		// Break frecency ties by character so which rows land where doesn't
+		// depend on the unordered SELECT's incidental row order.
+		scored.sort_by(|a, b| {
+			b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.1.Character.cmp(&b.1.Character))
+		});
+		Ok(scored.into_iter().map(|(_, entry)| entry).collect())
+	}
+
+	/// Every row in the `kaomoji` table, regardless of flags. Used by search,
+	/// which needs to see everything, not just one view.
+	pub fn LoadAll(&self) -> Result<Vec<KaomojiEntry>, String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+		loadWhere(&conn, "1 = 1", "character")
+	}
+
+	/// Upserts each entry into the user library (`saved = 1`), normalizing
+	/// character/category/tags the same way `SaveKaomoji` always has.
+	/// Applied inside one transaction; per-entry failures (e.g. an empty
+	/// character) don't abort the rest of the batch.
+	pub fn SaveUser(&self, entries: Vec<KaomojiEntry>) -> Result<Vec<Result<(), String>>, String> {
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		let results = entries
+			.into_iter()
+			.map(|entry| upsertKaomoji(&tx, entry, "saved"))
+			.collect();
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(results)
+	}
+
+	/// Marks each entry as recently used: increments its `use_count` and
+	/// bumps `last_used_millis` (inserting it fresh with a count of 1 if
+	/// it's new) rather than removing and re-inserting it. Afterwards, only
+	/// the top `limit` rows by frecency score stay marked `recent = 1`
+	/// (the rest simply have `recent` cleared; they stay in the `kaomoji`
+	/// table if saved or favorited elsewhere).
+	pub fn TouchRecents(&self, entries: Vec<KaomojiEntry>, limit: usize, halfLifeDays: f64) -> Result<Vec<Result<(), String>>, String> {
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+		let nowMs = nowMillis();
+
+		let results: Vec<Result<(), String>> = entries.into_iter().map(|entry| touchRecentRow(&tx, entry, nowMs)).collect();
+
+		let mut scored: Vec<(f64, String)> = {
+			let mut stmt = tx
+				.prepare("SELECT character, use_count, last_used_millis FROM kaomoji WHERE recent = 1")
+				.map_err(|e| e.to_string())?;
+			let rows = stmt
+				.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)))
+				.map_err(|e| e.to_string())?;
+
+			let mut scored = Vec::new();
+			for row in rows {
+				let (character, useCount, lastUsedMillis) = row.map_err(|e| e.to_string())?;
+				scored.push((frecencyScore(useCount, lastUsedMillis, nowMs, halfLifeDays), character));
+			}
+			scored
+		};
+		// Break frecency ties by character so which rows get clipped at the
+		// `limit` boundary doesn't depend on the unordered SELECT's
+		// incidental row order.
+		scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.1.cmp(&b.1)));
+
+		for (_, character) in scored.into_iter().skip(limit) {
+			tx.execute("UPDATE kaomoji SET recent = 0 WHERE character = ?1", params![character]).map_err(|e| e.to_string())?;
+		}
+
+		pruneOrphans(&tx)?;
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(results)
+	}
+
+	/// Toggles `favorite` for each entry, returning the new state. Entries
+	/// left with every flag cleared are pruned from the table entirely, the
+	/// same way they used to simply disappear from `kaomojis.favorites.json`.
+	pub fn ToggleFavorites(&self, entries: Vec<KaomojiEntry>) -> Result<Vec<Result<bool, String>>, String> {
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		let results = entries
+			.into_iter()
+			.map(|entry| -> Result<bool, String> {
+				let character = entry.Character.trim().to_string();
+				if character.is_empty() {
+					return Err("Kaomoji character cannot be empty".to_string());
+				}
+
+				let wasFavorite: Option<bool> = tx
+					.query_row("SELECT favorite FROM kaomoji WHERE character = ?1", params![character], |row| {
+						row.get::<_, i64>(0).map(|v| v != 0)
+					})
+					.ok();
+
+				let nowFavorite = !wasFavorite.unwrap_or(false);
+
+				if wasFavorite.is_none() {
+					// Not seen before: insert it fresh with favorite = 1.
+					upsertKaomoji(&tx, KaomojiEntry { Character: character.clone(), ..entry }, "favorite")?;
+				} else {
+					tx.execute(
+						"UPDATE kaomoji SET favorite = ?1 WHERE character = ?2",
+						params![nowFavorite as i64, character],
+					)
+					.map_err(|e| e.to_string())?;
+				}
+
+				Ok(nowFavorite)
+			})
+			.collect();
+
+		pruneOrphans(&tx)?;
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(results)
+	}
+
+	/// Every row with its `saved`/`favorite`/`recent` flags, for export.
+	pub fn LoadAllFlagged(&self) -> Result<Vec<FlaggedEntry>, String> {
+		let conn = self.conn.lock().map_err(|e| e.to_string())?;
+
+		let mut stmt = conn
+			.prepare("SELECT character, category, saved, favorite, recent, use_count, last_used_millis FROM kaomoji ORDER BY character")
+			.map_err(|e| e.to_string())?;
+		let rows = stmt
+			.query_map([], |row| {
+				Ok((
+					row.get::<_, String>(0)?,
+					row.get::<_, String>(1)?,
+					row.get::<_, i64>(2)? != 0,
+					row.get::<_, i64>(3)? != 0,
+					row.get::<_, i64>(4)? != 0,
+					row.get::<_, i64>(5)?,
+					row.get::<_, i64>(6)?,
+				))
+			})
+			.map_err(|e| e.to_string())?;
+
+		let mut list = Vec::new();
+		for row in rows {
+			let (character, category, saved, favorite, recent, useCount, lastUsedMillis) = row.map_err(|e| e.to_string())?;
+			let tags = loadTags(&conn, &character)?;
+			list.push(FlaggedEntry {
+				Character: character,
+				Tags: tags,
+				Category: category,
+				Saved: saved,
+				Favorite: favorite,
+				Recent: recent,
+				UseCount: useCount,
+				LastUsedMillis: lastUsedMillis,
+			});
+		}
+		Ok(list)
+	}
+
+	/// Wipes the store and replaces it wholesale with `rows` (import,
+	/// `MergeStrategy::Replace`). Returns how many rows actually landed,
+	/// which can be fewer than `rows.len()` if some had an empty `Character`.
+	pub fn ReplaceAllFlagged(&self, rows: Vec<FlaggedEntry>) -> Result<usize, String> {
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		tx.execute("DELETE FROM kaomoji_tag", []).map_err(|e| e.to_string())?;
+		tx.execute("DELETE FROM kaomoji", []).map_err(|e| e.to_string())?;
+		tx.execute("DELETE FROM tag", []).map_err(|e| e.to_string())?;
+
+		let mut applied = 0;
+		for row in rows {
+			if insertFlaggedRow(&tx, row)? {
+				applied += 1;
+			}
+		}
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(applied)
+	}
+
+	/// Upserts `rows` by `Character` into the existing store (import,
+	/// `MergeStrategy::Merge`): tags are unioned with whatever is already
+	/// saved, flags are OR'd, and category is overwritten by the import.
+	/// Returns how many rows actually landed, which can be fewer than
+	/// `rows.len()` if some had an empty `Character`.
+	pub fn MergeAllFlagged(&self, rows: Vec<FlaggedEntry>) -> Result<usize, String> {
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		let mut applied = 0;
+		for row in rows {
+			let character = row.Character.trim().to_string();
+			if character.is_empty() {
+				continue;
+			}
+
+			let existing: Option<(bool, bool, bool, i64, i64)> = tx
+				.query_row(
+					"SELECT saved, favorite, recent, use_count, last_used_millis FROM kaomoji WHERE character = ?1",
+					params![character],
+					|r| {
+						Ok((
+							r.get::<_, i64>(0)? != 0,
+							r.get::<_, i64>(1)? != 0,
+							r.get::<_, i64>(2)? != 0,
+							r.get::<_, i64>(3)?,
+							r.get::<_, i64>(4)?,
+						))
+					},
+				)
+				.ok();
+			let existingTags = loadTags(&tx, &character)?;
+
+			let mut unionedTags: std::collections::BTreeSet<String> = existingTags.into_iter().collect();
+			unionedTags.extend(row.Tags.iter().cloned());
+
+			let (existingSaved, existingFavorite, existingRecent, existingUseCount, existingLastUsedMillis) =
+				existing.unwrap_or((false, false, false, 0, 0));
+			let merged = FlaggedEntry {
+				Character: character,
+				Tags: unionedTags.into_iter().collect(),
+				Category: row.Category,
+				Saved: existingSaved || row.Saved,
+				Favorite: existingFavorite || row.Favorite,
+				Recent: existingRecent || row.Recent,
+				UseCount: existingUseCount.max(row.UseCount),
+				LastUsedMillis: existingLastUsedMillis.max(row.LastUsedMillis),
+			};
+			insertFlaggedRow(&tx, merged)?;
+			applied += 1;
+		}
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(applied)
+	}
+
+	/// Renames a tag across the whole library. If `to` already exists, `from`
+	/// is merged into it instead of colliding. Returns the number of entries
+	/// that carried `from`.
+	pub fn RenameTag(&self, from: &str, to: &str) -> Result<usize, String> {
+		let fromName = from.trim().to_lowercase();
+		let toName = to.trim().to_lowercase();
+		if fromName.is_empty() || toName.is_empty() {
+			return Err("Tag name cannot be empty".to_string());
+		}
+
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		let fromId: Option<i64> = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![fromName], |r| r.get(0)).ok();
+		let Some(fromId) = fromId else {
+			tx.commit().map_err(|e| e.to_string())?;
+			return Ok(0);
+		};
+		let affected = countEntriesForTag(&tx, fromId)?;
+
+		if fromName != toName {
+			let toId: Option<i64> = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![toName], |r| r.get(0)).ok();
+			match toId {
+				Some(toId) => mergeTagInto(&tx, fromId, toId)?,
+				None => {
+					tx.execute("UPDATE tag SET name = ?1 WHERE id = ?2", params![toName, fromId]).map_err(|e| e.to_string())?;
+				}
+			}
+		}
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(affected)
+	}
+
+	/// Merges every tag in `sources` into `into`, creating `into` if it
+	/// doesn't already exist. Returns the number of distinct entries that
+	/// carried any of `sources`.
+	pub fn MergeTags(&self, sources: Vec<String>, into: &str) -> Result<usize, String> {
+		let intoName = into.trim().to_lowercase();
+		if intoName.is_empty() {
+			return Err("Tag name cannot be empty".to_string());
+		}
+
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		tx.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", params![intoName]).map_err(|e| e.to_string())?;
+		let intoId: i64 = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![intoName], |r| r.get(0)).map_err(|e| e.to_string())?;
+
+		let mut affected: std::collections::HashSet<String> = std::collections::HashSet::new();
+		for source in sources {
+			let sourceName = source.trim().to_lowercase();
+			if sourceName.is_empty() || sourceName == intoName {
+				continue;
+			}
+
+			let sourceId: Option<i64> = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![sourceName], |r| r.get(0)).ok();
+			let Some(sourceId) = sourceId else {
+				continue;
+			};
+
+			affected.extend(entryCharactersForTag(&tx, sourceId)?);
+			mergeTagInto(&tx, sourceId, intoId)?;
+		}
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(affected.len())
+	}
+
+	/// Deletes a tag from the whole library. Returns the number of entries
+	/// that carried it.
+	pub fn DeleteTag(&self, tag: &str) -> Result<usize, String> {
+		let name = tag.trim().to_lowercase();
+		if name.is_empty() {
+			return Err("Tag name cannot be empty".to_string());
+		}
+
+		let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+		let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+		let tagId: Option<i64> = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![name], |r| r.get(0)).ok();
+		let Some(tagId) = tagId else {
+			tx.commit().map_err(|e| e.to_string())?;
+			return Ok(0);
+		};
+
+		let affected = countEntriesForTag(&tx, tagId)?;
+		tx.execute("DELETE FROM kaomoji_tag WHERE tag_id = ?1", params![tagId]).map_err(|e| e.to_string())?;
+		tx.execute("DELETE FROM tag WHERE id = ?1", params![tagId]).map_err(|e| e.to_string())?;
+
+		tx.commit().map_err(|e| e.to_string())?;
+		self.bumpRevision();
+		Ok(affected)
+	}
+}
+
+fn countEntriesForTag(tx: &rusqlite::Transaction, tagId: i64) -> Result<usize, String> {
+	tx.query_row("SELECT COUNT(*) FROM kaomoji_tag WHERE tag_id = ?1", params![tagId], |r| r.get::<_, i64>(0))
+		.map(|count| count as usize)
+		.map_err(|e| e.to_string())
+}
+
+fn entryCharactersForTag(tx: &rusqlite::Transaction, tagId: i64) -> Result<Vec<String>, String> {
+	let mut stmt = tx.prepare("SELECT character FROM kaomoji_tag WHERE tag_id = ?1").map_err(|e| e.to_string())?;
+	let rows = stmt.query_map(params![tagId], |r| r.get::<_, String>(0)).map_err(|e| e.to_string())?;
+
+	let mut characters = Vec::new();
+	for row in rows {
+		characters.push(row.map_err(|e| e.to_string())?);
+	}
+	Ok(characters)
+}
+
+/// Re-links every entry carrying `fromId` to `toId` instead (ignoring
+/// conflicts where an entry already carries both), then drops `fromId`.
+fn mergeTagInto(tx: &rusqlite::Transaction, fromId: i64, toId: i64) -> Result<(), String> {
+	tx.execute(
+		"INSERT OR IGNORE INTO kaomoji_tag (character, tag_id) SELECT character, ?1 FROM kaomoji_tag WHERE tag_id = ?2",
+		params![toId, fromId],
+	)
+	.map_err(|e| e.to_string())?;
+	tx.execute("DELETE FROM kaomoji_tag WHERE tag_id = ?1", params![fromId]).map_err(|e| e.to_string())?;
+	tx.execute("DELETE FROM tag WHERE id = ?1", params![fromId]).map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+/// A full row plus its membership flags, as stored in an export bundle.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[allow(non_snake_case)]
+pub struct FlaggedEntry {
+	pub Character: String,
+	pub Tags: Vec<String>,
+	pub Category: String,
+	pub Saved: bool,
+	pub Favorite: bool,
+	pub Recent: bool,
+	/// Frecency counters (see `frecencyScore`); carried through export/import
+	/// so backing up and restoring a library doesn't reset everyone's
+	/// `recent` ranking back to zero.
+	#[serde(default)]
+	pub UseCount: i64,
+	#[serde(default)]
+	pub LastUsedMillis: i64,
+}
+
+/// Inserts or updates a single flagged row. Returns `false` without writing
+/// anything if `row.Character` is empty.
+fn insertFlaggedRow(tx: &rusqlite::Transaction, mut row: FlaggedEntry) -> Result<bool, String> {
+	row.Character = row.Character.trim().to_string();
+	if row.Character.is_empty() {
+		return Ok(false);
+	}
+	row.Category = row.Category.trim().to_string();
+	let tags = crate::NormalizeTags(row.Tags);
+
+	tx.execute(
+		"INSERT INTO kaomoji (character, category, saved, favorite, recent, use_count, last_used_millis)
+		 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+		 ON CONFLICT(character) DO UPDATE SET
+			category = excluded.category,
+			saved = excluded.saved,
+			favorite = excluded.favorite,
+			recent = excluded.recent,
+			use_count = excluded.use_count,
+			last_used_millis = excluded.last_used_millis",
+		params![
+			row.Character,
+			row.Category,
+			row.Saved as i64,
+			row.Favorite as i64,
+			row.Recent as i64,
+			row.UseCount,
+			row.LastUsedMillis,
+		],
+	)
+	.map_err(|e| e.to_string())?;
+
+	replaceTags(tx, &row.Character, tags)?;
+	Ok(true)
+}
+
+/// Loads every row (plus its tags) matching `whereClause`, ordered by
+/// `orderBy`.
+fn loadWhere(conn: &Connection, whereClause: &str, orderBy: &str) -> Result<Vec<KaomojiEntry>, String> {
+	let sql = format!("SELECT character, category FROM kaomoji WHERE {whereClause} ORDER BY {orderBy}");
+	let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+	let rows = stmt
+		.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+		.map_err(|e| e.to_string())?;
+
+	let mut list = Vec::new();
+	for row in rows {
+		let (character, category) = row.map_err(|e| e.to_string())?;
+		let tags = loadTags(conn, &character)?;
+		list.push(KaomojiEntry { Character: character, Tags: tags, Category: category });
+	}
+	Ok(list)
+}
+
+fn loadTags(conn: &Connection, character: &str) -> Result<Vec<String>, String> {
+	let mut stmt = conn
+		.prepare("SELECT tag.name FROM tag JOIN kaomoji_tag ON kaomoji_tag.tag_id = tag.id WHERE kaomoji_tag.character = ?1 ORDER BY tag.name")
+		.map_err(|e| e.to_string())?;
+	let rows = stmt.query_map(params![character], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+
+	let mut tags = Vec::new();
+	for row in rows {
+		tags.push(row.map_err(|e| e.to_string())?);
+	}
+	Ok(tags)
+}
+
+/// Normalizes and upserts a single entry, setting `flagColumn = 1` (one of
+/// `saved`, `favorite`, `recent`). Existing flags on the row are preserved;
+/// only `category`/`tags`/the touched flag change.
+fn upsertKaomoji(tx: &rusqlite::Transaction, mut entry: KaomojiEntry, flagColumn: &str) -> Result<(), String> {
+	entry.Character = entry.Character.trim().to_string();
+	if entry.Character.is_empty() {
+		return Err("Kaomoji character cannot be empty".to_string());
+	}
+	entry.Category = entry.Category.trim().to_string();
+	let tags = crate::NormalizeTags(entry.Tags);
+
+	let sql = format!(
+		"INSERT INTO kaomoji (character, category, {flagColumn}) VALUES (?1, ?2, 1)
+		 ON CONFLICT(character) DO UPDATE SET category = excluded.category, {flagColumn} = 1"
+	);
+	tx.execute(&sql, params![entry.Character, entry.Category]).map_err(|e| e.to_string())?;
+
+	replaceTags(tx, &entry.Character, tags)
+}
+
+/// Replaces a row's tag set: clears its `kaomoji_tag` rows, then re-links it
+/// to (creating if needed) one `tag` row per normalized tag.
+fn replaceTags(tx: &rusqlite::Transaction, character: &str, tags: Vec<String>) -> Result<(), String> {
+	tx.execute("DELETE FROM kaomoji_tag WHERE character = ?1", params![character]).map_err(|e| e.to_string())?;
+	for tag in tags {
+		tx.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", params![tag]).map_err(|e| e.to_string())?;
+		let tagId: i64 = tx.query_row("SELECT id FROM tag WHERE name = ?1", params![tag], |row| row.get(0)).map_err(|e| e.to_string())?;
+		tx.execute(
+			"INSERT OR IGNORE INTO kaomoji_tag (character, tag_id) VALUES (?1, ?2)",
+			params![character, tagId],
+		)
+		.map_err(|e| e.to_string())?;
+	}
+	Ok(())
+}
+
+/// Deletes rows left with every flag cleared, mirroring how an entry used
+/// to vanish once removed from all three legacy JSON files.
+fn pruneOrphans(tx: &rusqlite::Transaction) -> Result<(), String> {
+	tx.execute("DELETE FROM kaomoji WHERE saved = 0 AND favorite = 0 AND recent = 0", [])
+		.map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+fn nowMillis() -> i64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+/// `useCount * decay(elapsed)`, where `decay` halves every `halfLifeDays`.
+/// A kaomoji used many times long ago still outranks one used once just
+/// now, but not forever.
+fn frecencyScore(useCount: i64, lastUsedMillis: i64, nowMillis: i64, halfLifeDays: f64) -> f64 {
+	const MILLIS_PER_DAY: f64 = 86_400_000.0;
+	let halfLifeMillis = halfLifeDays.max(0.01) * MILLIS_PER_DAY;
+	let elapsedMillis = (nowMillis - lastUsedMillis).max(0) as f64;
+	let decay = 0.5f64.powf(elapsedMillis / halfLifeMillis);
+	useCount as f64 * decay
+}
+
+/// Upserts a single recently-used entry: increments `use_count` and bumps
+/// `last_used_millis`, inserting it fresh (count 1) if it isn't already in
+/// the store. Recording a use is purely a frecency signal — it must never
+/// overwrite the category/tags a user set via `SaveKaomoji` or the tag
+/// editor, so an existing row keeps its `category` and tags untouched; only
+/// a brand-new row gets `entry`'s category as its starting value, since
+/// there's no existing metadata yet to clobber.
+fn touchRecentRow(tx: &rusqlite::Transaction, mut entry: KaomojiEntry, nowMs: i64) -> Result<(), String> {
+	entry.Character = entry.Character.trim().to_string();
+	if entry.Character.is_empty() {
+		return Err("Kaomoji character cannot be empty".to_string());
+	}
+	entry.Category = entry.Category.trim().to_string();
+
+	tx.execute(
+		"INSERT INTO kaomoji (character, category, recent, use_count, last_used_millis)
+		 VALUES (?1, ?2, 1, 1, ?3)
+		 ON CONFLICT(character) DO UPDATE SET
+			recent = 1,
+			use_count = kaomoji.use_count + 1,
+			last_used_millis = ?3",
+		params![entry.Character, entry.Category, nowMs],
+	)
+	.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Imports one legacy JSON file (if present) into the `kaomoji` table,
+/// setting `flagColumn = 1` for every entry it contains, then renames the
+/// original to `<name>.migrated` so it's never re-imported.
+fn importLegacyFile(conn: &Connection, path: &Path, flagColumn: &str) -> Result<(), String> {
+	if !path.exists() {
+		return Ok(());
+	}
+
+	let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+	let list: Vec<KaomojiEntry> = match serde_json::from_str(&content) {
+		Ok(v) => v,
+		Err(_) => {
+			BackupCorrupt(path);
+			return Ok(());
+		}
+	};
+
+	// Recents carry positional MRU order, but ranking is now by frecency
+	// score (`frecencyScore`), not array position. Seed a synthetic
+	// `use_count`/`last_used_millis` that decays with position so a
+	// migrated recents list still ranks in its original MRU order instead
+	// of collapsing to a 0-score tie.
+	let nowMs = nowMillis();
+	const MIGRATED_RECENT_SPACING_MILLIS: i64 = 60 * 60 * 1000;
+
+	let mut tagCache: HashMap<String, i64> = HashMap::new();
+	for (index, mut entry) in list.into_iter().enumerate() {
+		entry.Character = entry.Character.trim().to_string();
+		if entry.Character.is_empty() {
+			continue;
+		}
+		entry.Category = entry.Category.trim().to_string();
+		let tags = crate::NormalizeTags(entry.Tags);
+
+		// Each legacy file carries its own independent snapshot of
+		// category/tags, taken whenever that entry was last saved, recented,
+		// or favorited. If the row already exists from an earlier pass in
+		// this same migration, that pass's snapshot is the one the user
+		// actually edited most recently via `SaveKaomoji` (the "saved" pass
+		// always runs first) — a later pass's stale copy must not clobber
+		// it, the same principle `touchRecentRow` already applies to live
+		// `TouchRecents` calls.
+		let rowExisted: bool = conn
+			.query_row("SELECT 1 FROM kaomoji WHERE character = ?1", params![entry.Character], |_| Ok(()))
+			.optional()
+			.map_err(|e| e.to_string())?
+			.is_some();
+
+		if flagColumn == "recent" {
+			let lastUsedMillis = nowMs - index as i64 * MIGRATED_RECENT_SPACING_MILLIS;
+			if rowExisted {
+				conn.execute(
+					"UPDATE kaomoji SET recent = 1, use_count = 1, last_used_millis = ?2 WHERE character = ?1",
+					params![entry.Character, lastUsedMillis],
+				)
+				.map_err(|e| e.to_string())?;
+			} else {
+				conn.execute(
+					"INSERT INTO kaomoji (character, category, recent, use_count, last_used_millis) VALUES (?1, ?2, 1, 1, ?3)",
+					params![entry.Character, entry.Category, lastUsedMillis],
+				)
+				.map_err(|e| e.to_string())?;
+			}
+		} else if rowExisted {
+			let sql = format!("UPDATE kaomoji SET {flagColumn} = 1 WHERE character = ?1");
+			conn.execute(&sql, params![entry.Character]).map_err(|e| e.to_string())?;
+		} else {
+			let sql = format!("INSERT INTO kaomoji (character, category, {flagColumn}) VALUES (?1, ?2, 1)");
+			conn.execute(&sql, params![entry.Character, entry.Category]).map_err(|e| e.to_string())?;
+		}
+
+		// Likewise, only seed tags for a row this pass is the first to
+		// create; an earlier pass's tags (from a file last edited more
+		// recently) must not be wiped by this file's older copy.
+		if rowExisted {
+			continue;
+		}
+
+		for tag in tags {
+			let tagId = match tagCache.get(&tag) {
+				Some(id) => *id,
+				None => {
+					conn.execute("INSERT OR IGNORE INTO tag (name) VALUES (?1)", params![tag]).map_err(|e| e.to_string())?;
+					let id: i64 = conn.query_row("SELECT id FROM tag WHERE name = ?1", params![tag], |row| row.get(0)).map_err(|e| e.to_string())?;
+					tagCache.insert(tag.clone(), id);
+					id
+				}
+			};
+			conn.execute(
+				"INSERT OR IGNORE INTO kaomoji_tag (character, tag_id) VALUES (?1, ?2)",
+				params![entry.Character, tagId],
+			)
+			.map_err(|e| e.to_string())?;
+		}
+	}
+
+	let migratedPath = path.with_extension(match path.extension().and_then(|e| e.to_str()) {
+		Some(ext) => format!("{ext}.migrated"),
+		None => "migrated".to_string(),
+	});
+	std::fs::rename(path, migratedPath).map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{frecencyScore, Storage};
+	use crate::testutil::{openTempStorage, TempDir};
+	use crate::KaomojiEntry;
+
+	const ONE_DAY_MILLIS: i64 = 86_400_000;
+
+	fn entry(character: &str) -> KaomojiEntry {
+		KaomojiEntry { Character: character.to_string(), Tags: vec![], Category: "".to_string() }
+	}
+
+	#[test]
+	fn save_user_commits_valid_entries_and_only_errors_the_invalid_one() {
+		let (_dir, storage) = openTempStorage("save_user_mixed_batch");
+
+		let results = storage.SaveUser(vec![entry(":)"), entry("   "), entry(":(")]).unwrap();
+		assert!(results[0].is_ok());
+		assert!(results[1].is_err());
+		assert!(results[2].is_ok());
+
+		let saved: Vec<String> = storage.LoadUser().unwrap().into_iter().map(|e| e.Character).collect();
+		assert_eq!(saved, vec![":(".to_string(), ":)".to_string()]);
+	}
+
+	#[test]
+	fn toggle_favorites_commits_valid_entries_and_only_errors_the_invalid_one() {
+		let (_dir, storage) = openTempStorage("toggle_favorites_mixed_batch");
+
+		let results = storage.ToggleFavorites(vec![entry(":)"), entry("   ")]).unwrap();
+		assert_eq!(results[0], Ok(true));
+		assert!(results[1].is_err());
+
+		assert_eq!(storage.LoadFavorites().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn touch_recents_breaks_frecency_ties_by_character_deterministically() {
+		let (_dir, storage) = openTempStorage("touch_recents_tie_break");
+
+		// All three are brand-new entries touched in the same batch, so
+		// they land with identical use_count/last_used_millis and tie on
+		// frecency score. Which one gets clipped at `limit` must depend on
+		// `character`, not on SQLite's unordered row order.
+		storage.TouchRecents(vec![entry("c"), entry("a"), entry("b")], 2, 7.0).unwrap();
+
+		let kept: Vec<String> = storage.LoadRecents(7.0).unwrap().into_iter().map(|e| e.Character).collect();
+		assert_eq!(kept, vec!["a".to_string(), "b".to_string()]);
+	}
+
+	fn writeLegacyFile(dir: &std::path::Path, name: &str, entries: &[(&str, &str, &[&str])]) {
+		let json: Vec<KaomojiEntry> = entries
+			.iter()
+			.map(|(character, category, tags)| KaomojiEntry {
+				Character: character.to_string(),
+				Category: category.to_string(),
+				Tags: tags.iter().map(|t| t.to_string()).collect(),
+			})
+			.collect();
+		std::fs::write(dir.join(name), serde_json::to_string(&json).unwrap()).unwrap();
+	}
+
+	#[test]
+	fn migrate_from_json_keeps_the_saved_pass_category_and_tags() {
+		let dir = TempDir::new("disagreeing_snapshots");
+
+		// Three independently-stale snapshots of the same kaomoji, as if it
+		// picked up the "cute" tag and an "emotions" category after it was
+		// last recented/favorited but the library file was re-saved later.
+		writeLegacyFile(&dir.0, "kaomojis.user.json", &[(":)", "emotions", &["happy", "cute"])]);
+		writeLegacyFile(&dir.0, "kaomojis.recents.json", &[(":)", "old-category", &["happy"])]);
+		writeLegacyFile(&dir.0, "kaomojis.favorites.json", &[(":)", "stale", &["happy"])]);
+
+		let storage = Storage::Open(&dir.0.join("kaomojis.db")).unwrap();
+		storage.MigrateFromJson(&dir.0).unwrap();
+
+		let all = storage.LoadAll().unwrap();
+		assert_eq!(all.len(), 1);
+		assert_eq!(all[0].Category, "emotions");
+		assert_eq!(all[0].Tags, vec!["cute".to_string(), "happy".to_string()]);
+
+		// The later recent/favorite passes still flip their own flags...
+		assert_eq!(storage.LoadUser().unwrap().len(), 1);
+		assert_eq!(storage.LoadFavorites().unwrap().len(), 1);
+		assert_eq!(storage.LoadRecents(7.0).unwrap().len(), 1);
+
+		// ...without dragging the "cute" tag or the "emotions" category back
+		// down to their stale copies.
+		assert_eq!(storage.LoadFavorites().unwrap()[0].Category, "emotions");
+		assert_eq!(storage.LoadFavorites().unwrap()[0].Tags, vec!["cute".to_string(), "happy".to_string()]);
+	}
+
+	fn entryWithTags(character: &str, tags: &[&str]) -> KaomojiEntry {
+		KaomojiEntry { Character: character.to_string(), Tags: tags.iter().map(|t| t.to_string()).collect(), Category: "".to_string() }
+	}
+
+	fn tagsOf(storage: &Storage, character: &str) -> Vec<String> {
+		storage.LoadAll().unwrap().into_iter().find(|e| e.Character == character).unwrap().Tags
+	}
+
+	#[test]
+	fn rename_tag_merges_from_into_an_already_existing_to() {
+		let (_dir, storage) = openTempStorage("rename_tag_merge_collision");
+		storage.SaveUser(vec![entryWithTags(":)", &["cute"]), entryWithTags(":D", &["adorable"])]).unwrap();
+
+		let affected = storage.RenameTag("cute", "adorable").unwrap();
+		assert_eq!(affected, 1);
+
+		// ":)" loses "cute" in favor of the tag it collided with...
+		assert_eq!(tagsOf(&storage, ":)"), vec!["adorable".to_string()]);
+		// ...and ":D", which already carried "adorable", doesn't end up with
+		// a duplicate.
+		assert_eq!(tagsOf(&storage, ":D"), vec!["adorable".to_string()]);
+	}
+
+	#[test]
+	fn merge_tags_skips_a_source_equal_to_into() {
+		let (_dir, storage) = openTempStorage("merge_tags_skip_self");
+		storage.SaveUser(vec![entryWithTags(":)", &["into"])]).unwrap();
+
+		// "into" is both a source and the destination; it must be ignored
+		// rather than tripping over merging a tag into itself.
+		let affected = storage.MergeTags(vec!["into".to_string()], "into").unwrap();
+		assert_eq!(affected, 0);
+		assert_eq!(tagsOf(&storage, ":)"), vec!["into".to_string()]);
+	}
+
+	#[test]
+	fn merge_tags_dedups_affected_count_across_overlapping_sources() {
+		let (_dir, storage) = openTempStorage("merge_tags_dedup_affected");
+		storage.SaveUser(vec![entryWithTags(":)", &["a", "b"]), entryWithTags(":(", &["b"])]).unwrap();
+
+		// ":)" carries both "a" and "b"; naively summing each source's
+		// affected count would double-count it instead of the two distinct
+		// entries that actually carry either tag.
+		let affected = storage.MergeTags(vec!["a".to_string(), "b".to_string()], "combined").unwrap();
+		assert_eq!(affected, 2);
+		assert_eq!(tagsOf(&storage, ":)"), vec!["combined".to_string()]);
+		assert_eq!(tagsOf(&storage, ":("), vec!["combined".to_string()]);
+	}
+
+	#[test]
+	fn delete_tag_removes_it_from_every_entry_that_carried_it() {
+		let (_dir, storage) = openTempStorage("delete_tag_removes_from_all");
+		storage.SaveUser(vec![entryWithTags(":)", &["cute", "happy"]), entryWithTags(":(", &["cute"])]).unwrap();
+
+		let affected = storage.DeleteTag("cute").unwrap();
+		assert_eq!(affected, 2);
+		assert_eq!(tagsOf(&storage, ":)"), vec!["happy".to_string()]);
+		assert_eq!(tagsOf(&storage, ":("), Vec::<String>::new());
+	}
+
+	#[test]
+	fn frecency_score_is_full_use_count_at_zero_elapsed() {
+		assert_eq!(frecencyScore(5, 1_000, 1_000, 7.0), 5.0);
+	}
+
+	#[test]
+	fn frecency_score_halves_at_the_half_life() {
+		let score = frecencyScore(4, 0, 7 * ONE_DAY_MILLIS, 7.0);
+		assert!((score - 2.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn frecency_score_quarters_at_twice_the_half_life() {
+		let score = frecencyScore(4, 0, 14 * ONE_DAY_MILLIS, 7.0);
+		assert!((score - 1.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn frecency_score_ignores_future_last_used_instead_of_going_negative() {
+		// `lastUsedMillis` after `nowMillis` shouldn't happen, but clock skew
+		// or clamped test fixtures could produce it; elapsed is clamped to 0.
+		assert_eq!(frecencyScore(3, 2_000, 1_000, 7.0), 3.0);
+	}
+
+	#[test]
+	fn frecency_score_is_zero_for_never_used() {
+		assert_eq!(frecencyScore(0, 0, 7 * ONE_DAY_MILLIS, 7.0), 0.0);
+	}
+}
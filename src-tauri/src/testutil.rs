@@ -0,0 +1,41 @@
+/**
+ * Shared test fixtures.
+ *
+ * `TempDir`/`openTempStorage` were copy-pasted near-identically into
+ * storage.rs, export.rs, and recovery.rs's test modules; this is the one
+ * copy they all import instead.
+ *
+ * Author: KleaSCM
+ * Email: KleaSCM@gmail.com
+ */
+
+use crate::storage::Storage;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A fresh, uniquely-named directory under the system temp dir, cleaned up
+/// once the returned guard drops.
+#[allow(non_snake_case)]
+pub(crate) struct TempDir(pub(crate) std::path::PathBuf);
+
+impl TempDir {
+	pub(crate) fn new(label: &str) -> TempDir {
+		static COUNTER: AtomicU64 = AtomicU64::new(0);
+		let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+		let dir = std::env::temp_dir().join(format!("kao_test_{label}_{}_{n}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		TempDir(dir)
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.0);
+	}
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn openTempStorage(label: &str) -> (TempDir, Storage) {
+	let dir = TempDir::new(label);
+	let storage = Storage::Open(&dir.0.join("kaomojis.db")).unwrap();
+	(dir, storage)
+}